@@ -0,0 +1,324 @@
+use crate::span::{Span, SpanError, Spanned};
+use crate::token::Token;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Num(String),
+    Str(String),
+    Char(char),
+    Sym(String, Span),
+    Binary(String, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Mem(String, Option<Expr>),
+    Ret(Option<Expr>),
+    Print(Expr),
+    Loop(Vec<Stmt>),
+    Brk,
+    Jump(String),
+    Imp(String),
+    Asm(Vec<String>),
+    If(Expr, Vec<Stmt>, Option<Vec<Stmt>>),
+    FuncDef(String, Vec<String>, Vec<Stmt>),
+    Expr(Expr),
+}
+
+/// Parses a whole token stream (after macro expansion) into a tree of
+/// `Stmt`/`Expr` nodes, in place of the old neighbor-peeking linear walk, so
+/// codegen can evaluate nested expressions like `a + b * c` or
+/// `(a + b) * c` with correct precedence.
+pub fn parse_program(tokens: &[Spanned<Token>]) -> Result<Vec<Stmt>, SpanError> {
+    let mut parser = Parser::new(tokens);
+    let mut stmts = Vec::new();
+    while parser.peek().is_some() {
+        stmts.push(parser.parse_stmt()?);
+    }
+    Ok(stmts)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Spanned<Token>],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(tokens: &'a [Spanned<Token>]) -> Self {
+        Parser { tokens, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.node)
+    }
+
+    fn current_span(&self) -> Span {
+        self.tokens
+            .get(self.pos)
+            .map(|t| t.span)
+            .or_else(|| self.tokens.last().map(|t| t.span))
+            .unwrap_or(Span::new(0, 0))
+    }
+
+    fn advance(&mut self) -> Option<&Spanned<Token>> {
+        let tok = self.tokens.get(self.pos);
+        if tok.is_some() {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn expect(&mut self, want: &Token, what: &str) -> Result<(), SpanError> {
+        if self.peek() == Some(want) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(SpanError::new(
+                self.current_span().lo,
+                format!("expected {}", what),
+            ))
+        }
+    }
+
+    fn expect_semi(&mut self) -> Result<(), SpanError> {
+        self.expect(&Token::SemiColon, "`;`")
+    }
+
+    fn expect_identifier(&mut self, what: &str) -> Result<String, SpanError> {
+        match self.advance().map(|t| t.node.clone()) {
+            Some(Token::Identifier(name)) => Ok(name),
+            _ => Err(SpanError::new(self.current_span().lo, format!("expected {}", what))),
+        }
+    }
+
+    fn parse_stmt(&mut self) -> Result<Stmt, SpanError> {
+        if let Some(Token::Identifier(name)) = self.peek() {
+            match name.as_str() {
+                "mem" => return self.parse_mem(),
+                "ret" => return self.parse_ret(),
+                "print" => return self.parse_print(),
+                "loop" => return self.parse_loop(),
+                "brk" => {
+                    self.advance();
+                    self.expect_semi()?;
+                    return Ok(Stmt::Brk);
+                }
+                "jump" => return self.parse_jump(),
+                "imp" => return self.parse_imp(),
+                "asm" => return self.parse_asm(),
+                _ if self.looks_like_func_def() => return self.parse_func_def(),
+                _ => {}
+            }
+        }
+        self.parse_expr_stmt()
+    }
+
+    fn parse_mem(&mut self) -> Result<Stmt, SpanError> {
+        self.advance(); // `mem`
+        let name = self.expect_identifier("a variable name after `mem`")?;
+        let init = if matches!(self.peek(), Some(Token::SemiColon)) {
+            None
+        } else {
+            Some(self.parse_expr()?)
+        };
+        self.expect_semi()?;
+        Ok(Stmt::Mem(name, init))
+    }
+
+    fn parse_ret(&mut self) -> Result<Stmt, SpanError> {
+        self.advance(); // `ret`
+        let value = if matches!(self.peek(), Some(Token::SemiColon)) {
+            None
+        } else {
+            Some(self.parse_expr()?)
+        };
+        self.expect_semi()?;
+        Ok(Stmt::Ret(value))
+    }
+
+    fn parse_print(&mut self) -> Result<Stmt, SpanError> {
+        self.advance(); // `print`
+        let value = self.parse_expr()?;
+        self.expect_semi()?;
+        Ok(Stmt::Print(value))
+    }
+
+    fn parse_loop(&mut self) -> Result<Stmt, SpanError> {
+        self.advance(); // `loop`
+        let body = self.parse_block()?;
+        Ok(Stmt::Loop(body))
+    }
+
+    fn parse_jump(&mut self) -> Result<Stmt, SpanError> {
+        self.advance(); // `jump`
+        let label = self.expect_identifier("a label after `jump`")?;
+        self.expect_semi()?;
+        Ok(Stmt::Jump(label))
+    }
+
+    fn parse_imp(&mut self) -> Result<Stmt, SpanError> {
+        self.advance(); // `imp`
+        let path = self.expect_identifier("a path after `imp`")?;
+        self.expect_semi()?;
+        Ok(Stmt::Imp(path))
+    }
+
+    fn parse_asm(&mut self) -> Result<Stmt, SpanError> {
+        self.advance(); // `asm`
+        let mut parts = Vec::new();
+        while let Some(tok) = self.peek() {
+            match tok {
+                Token::SemiColon => break,
+                Token::Identifier(s) | Token::Number(s) => parts.push(s.clone()),
+                _ => {}
+            }
+            self.advance();
+        }
+        self.expect_semi()?;
+        Ok(Stmt::Asm(parts))
+    }
+
+    fn parse_func_def(&mut self) -> Result<Stmt, SpanError> {
+        let name = self.expect_identifier("a function name")?;
+        let mut params = Vec::new();
+        while let Some(Token::Identifier(p)) = self.peek() {
+            params.push(p.clone());
+            self.advance();
+        }
+        let body = self.parse_block()?;
+        Ok(Stmt::FuncDef(name, params, body))
+    }
+
+    fn parse_expr_stmt(&mut self) -> Result<Stmt, SpanError> {
+        let expr = self.parse_expr()?;
+        if matches!(self.peek(), Some(Token::Operation(op)) if op == "->") {
+            self.advance();
+            let then_body = self.parse_block()?;
+            let else_body = if matches!(self.peek(), Some(Token::Operation(op)) if op == "!->") {
+                self.advance();
+                Some(self.parse_block()?)
+            } else {
+                None
+            };
+            return Ok(Stmt::If(expr, then_body, else_body));
+        }
+        self.expect_semi()?;
+        Ok(Stmt::Expr(expr))
+    }
+
+    fn parse_block(&mut self) -> Result<Vec<Stmt>, SpanError> {
+        self.expect(&Token::BraceOpen, "`{`")?;
+        let mut stmts = Vec::new();
+        while !matches!(self.peek(), Some(Token::BraceClose) | None) {
+            stmts.push(self.parse_stmt()?);
+        }
+        self.expect(&Token::BraceClose, "`}`")?;
+        Ok(stmts)
+    }
+
+    /// Looks ahead for `IDENT IDENT* {`, the shape of a function definition,
+    /// without consuming anything.
+    fn looks_like_func_def(&self) -> bool {
+        let mut j = self.pos + 1;
+        while matches!(self.tokens.get(j).map(|t| &t.node), Some(Token::Identifier(_))) {
+            j += 1;
+        }
+        matches!(self.tokens.get(j).map(|t| &t.node), Some(Token::BraceOpen))
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, SpanError> {
+        self.parse_binary(0)
+    }
+
+    fn parse_binary(&mut self, min_prec: u8) -> Result<Expr, SpanError> {
+        let mut lhs = self.parse_primary()?;
+        while let Some(op) = self.peek_binop() {
+            let prec = binop_precedence(&op);
+            if prec < min_prec {
+                break;
+            }
+            self.advance();
+            let rhs = self.parse_binary(prec + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn peek_binop(&self) -> Option<String> {
+        match self.peek() {
+            Some(Token::Operation(op)) if is_binop(op) => Some(op.clone()),
+            _ => None,
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, SpanError> {
+        let span = self.current_span();
+        match self.peek().cloned() {
+            Some(Token::Number(n)) => {
+                self.advance();
+                Ok(Expr::Num(n))
+            }
+            Some(Token::StringLiteral(s)) => {
+                self.advance();
+                Ok(Expr::Str(s))
+            }
+            Some(Token::Char(c)) => {
+                self.advance();
+                Ok(Expr::Char(c))
+            }
+            Some(Token::Identifier(id)) if id == "ref" => {
+                self.advance();
+                let var_span = self.current_span();
+                let var = self.expect_identifier("a variable name after `ref`")?;
+                Ok(Expr::Sym(var, var_span))
+            }
+            Some(Token::Identifier(id))
+                if matches!(
+                    self.tokens.get(self.pos + 1).map(|t| &t.node),
+                    Some(Token::ParenOpen)
+                ) =>
+            {
+                self.advance(); // name
+                self.advance(); // `(`
+                let mut args = Vec::new();
+                if !matches!(self.peek(), Some(Token::ParenClose)) {
+                    loop {
+                        args.push(self.parse_expr()?);
+                        if matches!(self.peek(), Some(Token::Symbol(','))) {
+                            self.advance();
+                            continue;
+                        }
+                        break;
+                    }
+                }
+                self.expect(&Token::ParenClose, "`)`")?;
+                Ok(Expr::Call(id, args))
+            }
+            Some(Token::Identifier(id)) => {
+                self.advance();
+                Ok(Expr::Sym(id, span))
+            }
+            Some(Token::ParenOpen) => {
+                self.advance();
+                let inner = self.parse_expr()?;
+                self.expect(&Token::ParenClose, "`)`")?;
+                Ok(inner)
+            }
+            _ => Err(SpanError::new(span.lo, "expected an expression")),
+        }
+    }
+}
+
+fn is_binop(op: &str) -> bool {
+    matches!(op, "+" | "-" | "*" | "/" | "==" | "!=" | "<" | ">" | "<=" | ">=")
+}
+
+fn binop_precedence(op: &str) -> u8 {
+    match op {
+        "*" | "/" => 3,
+        "+" | "-" => 2,
+        "==" | "!=" | "<" | ">" | "<=" | ">=" => 1,
+        _ => 0,
+    }
+}
@@ -0,0 +1,382 @@
+use crate::ast::{Expr, Stmt};
+use crate::span::SpanError;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// A comparison kind, mirroring `std::cmp::Ordering` but also covering the
+/// negated and combined forms (`!=`, `<=`, `>=`) OwO-lang's operators need.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Num(i64),
+    Str(String),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instr {
+    NumPush(i64),
+    StrPush(String),
+    Load(String),
+    Store(String),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Cmp(Comparison),
+    Jump(usize),
+    JumpIfZero(usize),
+    /// Marks a function entry point so `Call` can find it by name; a no-op
+    /// at runtime.
+    Label(String),
+    Call(String),
+    Ret,
+    Print,
+}
+
+struct Ctx {
+    instrs: Vec<Instr>,
+    loop_breaks: Vec<Vec<usize>>,
+}
+
+/// Lowers the `Stmt`/`Expr` tree to a flat `Instr` program for the portable
+/// stack-machine VM, the `-fmbyas` build target. Mirrors the control-flow
+/// bookkeeping `generate_asm_target` does -- nested `if`/`loop` bodies
+/// become patched jump offsets into the instruction vector.
+pub fn generate_bytecode(program: &[Stmt]) -> Result<Vec<Instr>, SpanError> {
+    let mut ctx = Ctx {
+        instrs: Vec::new(),
+        loop_breaks: Vec::new(),
+    };
+
+    for stmt in program {
+        gen_stmt(stmt, &mut ctx)?;
+    }
+
+    Ok(ctx.instrs)
+}
+
+fn gen_stmt(stmt: &Stmt, ctx: &mut Ctx) -> Result<(), SpanError> {
+    match stmt {
+        Stmt::Mem(name, init) => {
+            match init {
+                Some(expr) => gen_expr(expr, ctx)?,
+                None => ctx.instrs.push(Instr::NumPush(0)),
+            }
+            ctx.instrs.push(Instr::Store(name.clone()));
+        }
+
+        Stmt::Ret(value) => {
+            if let Some(expr) = value {
+                gen_expr(expr, ctx)?;
+            }
+            ctx.instrs.push(Instr::Ret);
+        }
+
+        Stmt::Print(expr) => {
+            gen_expr(expr, ctx)?;
+            ctx.instrs.push(Instr::Print);
+        }
+
+        Stmt::Loop(body) => {
+            let start = ctx.instrs.len();
+            ctx.loop_breaks.push(Vec::new());
+            for s in body {
+                gen_stmt(s, ctx)?;
+            }
+            ctx.instrs.push(Instr::Jump(start));
+            let breaks = ctx.loop_breaks.pop().unwrap_or_default();
+            let end = ctx.instrs.len();
+            for b in breaks {
+                patch_jump(&mut ctx.instrs, b, end);
+            }
+        }
+
+        Stmt::Brk => {
+            if let Some(breaks) = ctx.loop_breaks.last_mut() {
+                breaks.push(ctx.instrs.len());
+                ctx.instrs.push(Instr::Jump(usize::MAX)); // patched when the loop closes
+            }
+        }
+
+        Stmt::Jump(label) => {
+            // No bare goto-by-label in the instruction set, so a `jump` is
+            // lowered to a call of that label.
+            ctx.instrs.push(Instr::Call(label.clone()));
+        }
+
+        Stmt::Imp(_) => {}
+
+        Stmt::Asm(_) => {
+            // Raw assembly has no bytecode equivalent; skip it.
+        }
+
+        Stmt::If(cond, then_body, else_body) => {
+            gen_expr(cond, ctx)?;
+            let jz_idx = ctx.instrs.len();
+            ctx.instrs.push(Instr::JumpIfZero(usize::MAX)); // patched below
+
+            for s in then_body {
+                gen_stmt(s, ctx)?;
+            }
+
+            match else_body {
+                Some(else_body) => {
+                    let jmp_idx = ctx.instrs.len();
+                    ctx.instrs.push(Instr::Jump(usize::MAX)); // patched at end, skips the else branch
+                    let else_start = ctx.instrs.len();
+                    patch_jump(&mut ctx.instrs, jz_idx, else_start);
+
+                    for s in else_body {
+                        gen_stmt(s, ctx)?;
+                    }
+
+                    let end = ctx.instrs.len();
+                    patch_jump(&mut ctx.instrs, jmp_idx, end);
+                }
+                None => {
+                    let end = ctx.instrs.len();
+                    patch_jump(&mut ctx.instrs, jz_idx, end);
+                }
+            }
+        }
+
+        Stmt::FuncDef(name, params, body) => {
+            ctx.instrs.push(Instr::Label(name.clone()));
+            // Args arrive on the operand stack in call order, so the last
+            // one pushed is the last parameter.
+            for param in params.iter().rev() {
+                ctx.instrs.push(Instr::Store(param.clone()));
+            }
+            for s in body {
+                gen_stmt(s, ctx)?;
+            }
+        }
+
+        Stmt::Expr(expr) => {
+            gen_expr(expr, ctx)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn gen_expr(expr: &Expr, ctx: &mut Ctx) -> Result<(), SpanError> {
+    match expr {
+        Expr::Num(n) => ctx.instrs.push(Instr::NumPush(parse_num(n))),
+        Expr::Str(s) => ctx.instrs.push(Instr::StrPush(s.clone())),
+        Expr::Char(c) => ctx.instrs.push(Instr::NumPush(*c as i64)),
+        Expr::Sym(name, _span) => ctx.instrs.push(Instr::Load(name.clone())),
+        Expr::Binary(op, lhs, rhs) => {
+            gen_expr(lhs, ctx)?;
+            gen_expr(rhs, ctx)?;
+            ctx.instrs.push(binop_instr(op));
+        }
+        Expr::Call(name, args) => {
+            for arg in args {
+                gen_expr(arg, ctx)?;
+            }
+            ctx.instrs.push(Instr::Call(name.clone()));
+        }
+    }
+
+    Ok(())
+}
+
+fn binop_instr(op: &str) -> Instr {
+    match op {
+        "+" => Instr::Add,
+        "-" => Instr::Sub,
+        "*" => Instr::Mul,
+        "/" => Instr::Div,
+        "==" => Instr::Cmp(Comparison::Eq),
+        "!=" => Instr::Cmp(Comparison::Ne),
+        "<" => Instr::Cmp(Comparison::Lt),
+        ">" => Instr::Cmp(Comparison::Gt),
+        "<=" => Instr::Cmp(Comparison::Le),
+        ">=" => Instr::Cmp(Comparison::Ge),
+        _ => unreachable!("the parser only ever emits known binary operators"),
+    }
+}
+
+/// Decodes a `Token::Number`'s source text, which may be decimal, `0x`/`0b`
+/// prefixed, contain `_` digit separators, or be a float -- floats truncate
+/// to the VM's integer `Value::Num`. `pub(crate)` so the asm backend can
+/// reuse it instead of passing the raw source text straight through to `mov`.
+pub(crate) fn parse_num(n: &str) -> i64 {
+    let n = &n.replace('_', "");
+    if let Some(hex) = n.strip_prefix("0x").or_else(|| n.strip_prefix("0X")) {
+        return i64::from_str_radix(hex, 16).unwrap_or(0);
+    }
+    if let Some(bin) = n.strip_prefix("0b").or_else(|| n.strip_prefix("0B")) {
+        return i64::from_str_radix(bin, 2).unwrap_or(0);
+    }
+    if n.contains('.') {
+        return n.parse::<f64>().unwrap_or(0.0) as i64;
+    }
+    n.parse().unwrap_or(0)
+}
+
+fn patch_jump(instrs: &mut [Instr], idx: usize, target: usize) {
+    match &mut instrs[idx] {
+        Instr::Jump(t) | Instr::JumpIfZero(t) => *t = target,
+        _ => {}
+    }
+}
+
+/// Runs a bytecode program on a small stack machine: an operand stack for
+/// expression evaluation and a name-keyed variable environment for `mem`/`ref`.
+pub fn run(program: &[Instr]) {
+    let mut stack: Vec<Value> = Vec::new();
+    let mut vars: HashMap<String, Value> = HashMap::new();
+    let mut call_stack: Vec<usize> = Vec::new();
+
+    let labels: HashMap<&str, usize> = program
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, instr)| match instr {
+            Instr::Label(name) => Some((name.as_str(), idx)),
+            _ => None,
+        })
+        .collect();
+
+    let mut ip = 0;
+    while ip < program.len() {
+        match &program[ip] {
+            Instr::NumPush(n) => stack.push(Value::Num(*n)),
+            Instr::StrPush(s) => stack.push(Value::Str(s.clone())),
+
+            Instr::Load(name) => {
+                let val = vars.get(name).cloned().unwrap_or(Value::Num(0));
+                stack.push(val);
+            }
+            Instr::Store(name) => {
+                if let Some(val) = stack.pop() {
+                    vars.insert(name.clone(), val);
+                }
+            }
+
+            Instr::Add | Instr::Sub | Instr::Mul | Instr::Div => {
+                let rhs = stack.pop().unwrap_or(Value::Num(0));
+                let lhs = stack.pop().unwrap_or(Value::Num(0));
+                stack.push(apply_arith(&program[ip], lhs, rhs));
+            }
+
+            Instr::Cmp(cmp) => {
+                let rhs = stack.pop().unwrap_or(Value::Num(0));
+                let lhs = stack.pop().unwrap_or(Value::Num(0));
+                stack.push(Value::Bool(apply_cmp(*cmp, lhs, rhs)));
+            }
+
+            Instr::Jump(target) => {
+                ip = *target;
+                continue;
+            }
+            Instr::JumpIfZero(target) => {
+                let cond = stack.pop().unwrap_or(Value::Num(0));
+                if is_falsy(&cond) {
+                    ip = *target;
+                    continue;
+                }
+            }
+
+            Instr::Label(_) => {}
+
+            Instr::Call(name) => {
+                if let Some(&target) = labels.get(name.as_str()) {
+                    call_stack.push(ip + 1);
+                    ip = target + 1; // skip past the Label marker itself
+                    continue;
+                }
+            }
+            Instr::Ret => match call_stack.pop() {
+                Some(ret_ip) => {
+                    ip = ret_ip;
+                    continue;
+                }
+                None => return,
+            },
+
+            Instr::Print => {
+                if let Some(val) = stack.pop() {
+                    println!("{}", format_value(&val));
+                }
+            }
+        }
+        ip += 1;
+    }
+}
+
+fn apply_arith(instr: &Instr, lhs: Value, rhs: Value) -> Value {
+    let (a, b) = (as_num(&lhs), as_num(&rhs));
+    let result = match instr {
+        Instr::Add => a + b,
+        Instr::Sub => a - b,
+        Instr::Mul => a * b,
+        Instr::Div if b != 0 => a / b,
+        Instr::Div => 0,
+        _ => 0,
+    };
+    Value::Num(result)
+}
+
+fn apply_cmp(cmp: Comparison, lhs: Value, rhs: Value) -> bool {
+    let ordering = as_num(&lhs).cmp(&as_num(&rhs));
+    match cmp {
+        Comparison::Eq => ordering == Ordering::Equal,
+        Comparison::Ne => ordering != Ordering::Equal,
+        Comparison::Lt => ordering == Ordering::Less,
+        Comparison::Gt => ordering == Ordering::Greater,
+        Comparison::Le => ordering != Ordering::Greater,
+        Comparison::Ge => ordering != Ordering::Less,
+    }
+}
+
+fn as_num(v: &Value) -> i64 {
+    match v {
+        Value::Num(n) => *n,
+        Value::Bool(b) => *b as i64,
+        Value::Str(_) => 0,
+    }
+}
+
+fn is_falsy(v: &Value) -> bool {
+    matches!(v, Value::Num(0) | Value::Bool(false))
+}
+
+fn format_value(v: &Value) -> String {
+    match v {
+        Value::Num(n) => n.to_string(),
+        Value::Str(s) => s.clone(),
+        Value::Bool(b) => b.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_num_decodes_hex() {
+        assert_eq!(parse_num("0xFF"), 255);
+    }
+
+    #[test]
+    fn parse_num_truncates_float() {
+        assert_eq!(parse_num("3.14"), 3);
+    }
+
+    #[test]
+    fn parse_num_strips_digit_separators() {
+        assert_eq!(parse_num("1_000"), 1000);
+    }
+}
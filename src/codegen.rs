@@ -0,0 +1,301 @@
+use crate::ast::{Expr, Stmt};
+use crate::span::SpanError;
+use std::collections::HashMap;
+
+struct Ctx {
+    body: String,
+    strings: Vec<(String, String)>,
+    reg_size: i32,
+    stack_offset: i32,
+    vars: HashMap<String, i32>,
+    label_counter: usize,
+    loop_ends: Vec<String>,
+}
+
+/// Walks the `Stmt`/`Expr` tree and emits a complete, assemblable NASM
+/// program: every string literal collected into `section .data`, an empty
+/// `section .bss` for future scratch space, a generated `_start` that calls
+/// `main` and exits with its return value, and only then the generated
+/// function bodies in `section .text`. Expressions are evaluated in
+/// post-order, spilling a compound left-hand side to the stack around a
+/// compound right-hand side, so nested expressions like `a + b * c` or
+/// `(a + b) * c` get correct code instead of the old neighbor-peeking
+/// scheme's wrong answers.
+pub fn generate_asm_target(program: &[Stmt], double: bool) -> Result<String, SpanError> {
+    let mut ctx = Ctx {
+        body: String::new(),
+        strings: Vec::new(),
+        reg_size: if double { 8 } else { 4 },
+        stack_offset: 0,
+        vars: HashMap::new(),
+        label_counter: 0,
+        loop_ends: Vec::new(),
+    };
+
+    for stmt in program {
+        gen_stmt(stmt, &mut ctx)?;
+    }
+
+    let mut out = String::new();
+
+    out += "section .data\n";
+    for (label, text) in &ctx.strings {
+        out += &format!("{}: db {}, 0\n", label, escape_for_db(text));
+    }
+    out += "\n";
+
+    out += "section .bss\n\n";
+
+    out += "global _start\n";
+    out += "section .text\n";
+    out += "_start:\n";
+    out += "    call main\n";
+    out += "    mov rdi, rax\n";
+    out += "    mov rax, 60\n";
+    out += "    syscall\n\n";
+
+    out += &ctx.body;
+
+    Ok(out)
+}
+
+/// Renders a string literal as a comma-separated NASM `db` argument list,
+/// splitting out newlines, carriage returns, tabs and quotes as their raw
+/// byte values since they can't appear inside a single quoted-string
+/// directive.
+fn escape_for_db(s: &str) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    fn flush(parts: &mut Vec<String>, current: &mut String) {
+        if !current.is_empty() {
+            parts.push(format!("\"{}\"", current));
+            current.clear();
+        }
+    }
+
+    for c in s.chars() {
+        match c {
+            '\n' => {
+                flush(&mut parts, &mut current);
+                parts.push("10".to_string());
+            }
+            '\r' => {
+                flush(&mut parts, &mut current);
+                parts.push("13".to_string());
+            }
+            '\t' => {
+                flush(&mut parts, &mut current);
+                parts.push("9".to_string());
+            }
+            '"' => {
+                flush(&mut parts, &mut current);
+                parts.push("34".to_string());
+            }
+            _ => current.push(c),
+        }
+    }
+    flush(&mut parts, &mut current);
+
+    if parts.is_empty() {
+        parts.push("\"\"".to_string());
+    }
+    parts.join(", ")
+}
+
+fn gen_stmt(stmt: &Stmt, ctx: &mut Ctx) -> Result<(), SpanError> {
+    match stmt {
+        Stmt::Mem(name, init) => {
+            ctx.stack_offset -= ctx.reg_size;
+            let off = ctx.stack_offset;
+            ctx.vars.insert(name.clone(), off);
+            if let Some(expr) = init {
+                gen_expr(expr, ctx)?;
+                ctx.body += &format!("    mov [rbp{}], rax\n", off);
+            }
+        }
+
+        Stmt::Ret(value) => {
+            if let Some(expr) = value {
+                gen_expr(expr, ctx)?;
+            }
+            ctx.body += "    leave\n    ret\n";
+        }
+
+        Stmt::Print(expr) => {
+            // The asm target has no print primitive; leave the value in rax
+            // with a comment marking the intent, same treatment as `imp`.
+            gen_expr(expr, ctx)?;
+            ctx.body += "    ; print rax\n";
+        }
+
+        Stmt::Loop(body) => {
+            let start_label = format!(".loop_start{}", ctx.label_counter);
+            let end_label = format!(".loop_end{}", ctx.label_counter);
+            ctx.label_counter += 1;
+
+            ctx.body += &format!("{}:\n", start_label);
+            ctx.loop_ends.push(end_label.clone());
+            for s in body {
+                gen_stmt(s, ctx)?;
+            }
+            ctx.loop_ends.pop();
+            ctx.body += &format!("    jmp {}\n", start_label);
+            ctx.body += &format!("{}:\n", end_label);
+        }
+
+        Stmt::Brk => {
+            if let Some(end_label) = ctx.loop_ends.last() {
+                ctx.body += &format!("    jmp {}\n", end_label);
+            }
+        }
+
+        Stmt::Jump(label) => {
+            ctx.body += &format!("    jmp {}\n", label);
+        }
+
+        Stmt::Imp(path) => {
+            ctx.body += &format!("    ; import {}\n", path);
+        }
+
+        Stmt::Asm(parts) => {
+            ctx.body += &parts.join(" ");
+            ctx.body += "\n";
+        }
+
+        Stmt::If(cond, then_body, else_body) => {
+            gen_expr(cond, ctx)?;
+            let else_label = format!(".if_else{}", ctx.label_counter);
+            let end_label = format!(".if_end{}", ctx.label_counter);
+            ctx.label_counter += 1;
+
+            let first_target = if else_body.is_some() { &else_label } else { &end_label };
+            ctx.body += &format!("    cmp rax, 0\n    je {}\n", first_target);
+
+            for s in then_body {
+                gen_stmt(s, ctx)?;
+            }
+
+            if let Some(else_body) = else_body {
+                ctx.body += &format!("    jmp {}\n{}:\n", end_label, else_label);
+                for s in else_body {
+                    gen_stmt(s, ctx)?;
+                }
+            }
+
+            ctx.body += &format!("{}:\n", end_label);
+        }
+
+        Stmt::FuncDef(name, params, body) => {
+            ctx.body += &format!("{}:\n    push rbp\n    mov rbp, rsp\n", name);
+            ctx.stack_offset = 0;
+            for (idx, param) in params.iter().enumerate() {
+                ctx.stack_offset -= ctx.reg_size;
+                ctx.vars.insert(param.clone(), ctx.stack_offset);
+                ctx.body += &format!("    ; arg {} at [rbp{}]\n", param, ctx.stack_offset);
+                // The caller pushed args last-to-first, so arg `idx` sits
+                // above the saved rbp and return address at [rbp+16+8*idx].
+                ctx.body += &format!(
+                    "    mov rax, [rbp+{}]\n    mov [rbp{}], rax\n",
+                    16 + idx * 8,
+                    ctx.stack_offset
+                );
+            }
+            for s in body {
+                gen_stmt(s, ctx)?;
+            }
+        }
+
+        Stmt::Expr(expr) => {
+            gen_expr(expr, ctx)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn gen_expr(expr: &Expr, ctx: &mut Ctx) -> Result<(), SpanError> {
+    match expr {
+        Expr::Num(n) => {
+            // `n` is whatever the tokenizer accepted (hex, binary, floats);
+            // NASM only understands a plain integer immediate, so decode
+            // through the same literal parser the `-fmbyas` VM uses rather
+            // than passing the source text through.
+            ctx.body += &format!("    mov rax, {}\n", crate::bytecode::parse_num(n));
+        }
+
+        Expr::Char(_) => {
+            // Char literals aren't lowered to bytes yet (unchanged from the
+            // token-walk codegen this replaces).
+        }
+
+        Expr::Str(s) => {
+            // Strings are collected into `section .data` rather than
+            // inlined here, so the label is just referenced by address.
+            // No leading `.`: a dot-prefixed label would bind to whichever
+            // non-local label precedes it, not to this `.data` symbol.
+            let label = format!("str{}", ctx.label_counter);
+            ctx.label_counter += 1;
+            ctx.strings.push((label.clone(), s.clone()));
+            ctx.body += &format!("    lea rax, [{}]\n", label);
+        }
+
+        Expr::Sym(name, span) => {
+            let off = *ctx.vars.get(name).ok_or_else(|| {
+                SpanError::new(span.lo, format!("undefined variable `{}`", name))
+            })?;
+            ctx.body += &format!("    mov rax, [rbp{}]\n", off);
+        }
+
+        Expr::Binary(op, lhs, rhs) => {
+            gen_expr(lhs, ctx)?;
+            match rhs.as_ref() {
+                Expr::Num(n) => {
+                    ctx.body += &format!("    mov rbx, {}\n", crate::bytecode::parse_num(n));
+                }
+                Expr::Sym(name, span) => {
+                    let off = *ctx.vars.get(name).ok_or_else(|| {
+                        SpanError::new(span.lo, format!("undefined variable `{}`", name))
+                    })?;
+                    ctx.body += &format!("    mov rbx, [rbp{}]\n", off);
+                }
+                _ => {
+                    // Compound right-hand side: spill lhs around it.
+                    ctx.body += "    push rax\n";
+                    gen_expr(rhs, ctx)?;
+                    ctx.body += "    mov rbx, rax\n    pop rax\n";
+                }
+            }
+            ctx.body += &format!("    {}\n", binop_instr(op));
+        }
+
+        Expr::Call(name, args) => {
+            for arg in args.iter().rev() {
+                gen_expr(arg, ctx)?;
+                ctx.body += "    push rax\n";
+            }
+            ctx.body += &format!("    call {}\n", name);
+            if !args.is_empty() {
+                ctx.body += &format!("    add rsp, {}\n", args.len() * 8);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn binop_instr(op: &str) -> &'static str {
+    match op {
+        "+" => "add rax, rbx",
+        "-" => "sub rax, rbx",
+        "*" => "imul rax, rbx",
+        "/" => "cqo\n    idiv rbx",
+        "==" => "cmp rax, rbx\n    sete al\n    movzx rax, al",
+        "!=" => "cmp rax, rbx\n    setne al\n    movzx rax, al",
+        "<" => "cmp rax, rbx\n    setl al\n    movzx rax, al",
+        ">" => "cmp rax, rbx\n    setg al\n    movzx rax, al",
+        "<=" => "cmp rax, rbx\n    setle al\n    movzx rax, al",
+        ">=" => "cmp rax, rbx\n    setge al\n    movzx rax, al",
+        _ => "",
+    }
+}
@@ -0,0 +1,259 @@
+use crate::span::{SpanError, Spanned};
+use crate::token::Token;
+use std::collections::HashMap;
+
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+#[derive(Debug, Clone)]
+struct MacroDef {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+/// Expands `macro NAME(a, b) { ... }` definitions inline at every call site.
+/// Runs as a token-to-token rewrite after `tokenize`, so an expanded body
+/// still carries the span of its invocation -- the call site becomes the
+/// blame target for anything that goes wrong inside it, the same fallback
+/// proc-macro2 uses when it can't trace a span through a macro expansion.
+pub fn expand_macros(tokens: Vec<Spanned<Token>>) -> Result<Vec<Spanned<Token>>, SpanError> {
+    let mut macros: HashMap<String, MacroDef> = HashMap::new();
+    let mut label_counter = 0;
+
+    let stripped = collect_definitions(tokens, &mut macros)?;
+    expand(&stripped, &macros, &mut label_counter, 0)
+}
+
+/// Pulls every `macro NAME(params) { body }` definition out of the token
+/// stream and into `macros`, returning the remaining tokens in order.
+fn collect_definitions(
+    tokens: Vec<Spanned<Token>>,
+    macros: &mut HashMap<String, MacroDef>,
+) -> Result<Vec<Spanned<Token>>, SpanError> {
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let is_macro_kw = matches!(&tokens[i].node, Token::Identifier(id) if id == "macro");
+        if !is_macro_kw {
+            out.push(tokens[i].clone());
+            i += 1;
+            continue;
+        }
+
+        let def_start = tokens[i].span.lo;
+        let name = match tokens.get(i + 1).map(|t| &t.node) {
+            Some(Token::Identifier(name)) => name.clone(),
+            _ => {
+                return Err(SpanError::new(
+                    def_start,
+                    "expected macro name after `macro`",
+                ))
+            }
+        };
+
+        let mut j = i + 2;
+        if !matches!(tokens.get(j).map(|t| &t.node), Some(Token::ParenOpen)) {
+            return Err(SpanError::new(def_start, "expected `(` after macro name"));
+        }
+        j += 1;
+
+        let mut params = Vec::new();
+        loop {
+            match tokens.get(j) {
+                Some(Spanned {
+                    node: Token::Identifier(param),
+                    ..
+                }) => {
+                    params.push(param.clone());
+                    j += 1;
+                }
+                Some(Spanned {
+                    node: Token::Symbol(','),
+                    ..
+                }) => {
+                    j += 1;
+                }
+                Some(Spanned {
+                    node: Token::ParenClose,
+                    ..
+                }) => {
+                    j += 1;
+                    break;
+                }
+                Some(tok) => {
+                    return Err(SpanError::new(
+                        tok.span.lo,
+                        "unexpected token in macro parameter list",
+                    ))
+                }
+                None => return Err(SpanError::new(def_start, "unterminated macro parameter list")),
+            }
+        }
+
+        if !matches!(tokens.get(j).map(|t| &t.node), Some(Token::BraceOpen)) {
+            return Err(SpanError::new(def_start, "expected `{` to start macro body"));
+        }
+        j += 1;
+
+        let body_start = j;
+        let mut depth = 1;
+        while j < tokens.len() && depth > 0 {
+            match &tokens[j].node {
+                Token::BraceOpen => depth += 1,
+                Token::BraceClose => depth -= 1,
+                _ => {}
+            }
+            if depth > 0 {
+                j += 1;
+            }
+        }
+        if depth != 0 {
+            return Err(SpanError::new(def_start, "unterminated macro body"));
+        }
+
+        let body: Vec<Token> = tokens[body_start..j]
+            .iter()
+            .map(|t| t.node.clone())
+            .collect();
+        macros.insert(name, MacroDef { params, body });
+
+        i = j + 1; // skip the closing brace
+    }
+
+    Ok(out)
+}
+
+/// Substitutes every call site of a known macro with its (hygienically
+/// renamed) body, recursing into the expansion so one macro may call
+/// another, bounded by `MAX_EXPANSION_DEPTH` to reject infinite
+/// self-reference.
+fn expand(
+    tokens: &[Spanned<Token>],
+    macros: &HashMap<String, MacroDef>,
+    label_counter: &mut usize,
+    depth: usize,
+) -> Result<Vec<Spanned<Token>>, SpanError> {
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let call = match &tokens[i].node {
+            Token::Identifier(name) => macros.get(name).map(|def| (name.clone(), def.clone())),
+            _ => None,
+        };
+
+        let (name, def) = match call {
+            Some(pair)
+                if matches!(tokens.get(i + 1).map(|t| &t.node), Some(Token::ParenOpen)) =>
+            {
+                pair
+            }
+            _ => {
+                out.push(tokens[i].clone());
+                i += 1;
+                continue;
+            }
+        };
+
+        if depth >= MAX_EXPANSION_DEPTH {
+            return Err(SpanError::new(
+                tokens[i].span.lo,
+                format!(
+                    "macro `{}` exceeded the recursion limit ({})",
+                    name, MAX_EXPANSION_DEPTH
+                ),
+            ));
+        }
+
+        let call_span = tokens[i].span;
+        let mut j = i + 2; // past name and '('
+        let mut args: Vec<Vec<Token>> = Vec::new();
+        let mut current_arg = Vec::new();
+        let mut paren_depth = 1;
+
+        while j < tokens.len() && paren_depth > 0 {
+            match &tokens[j].node {
+                Token::ParenOpen => {
+                    paren_depth += 1;
+                    current_arg.push(tokens[j].node.clone());
+                }
+                Token::ParenClose => {
+                    paren_depth -= 1;
+                    if paren_depth == 0 {
+                        if !current_arg.is_empty() {
+                            args.push(std::mem::take(&mut current_arg));
+                        }
+                    } else {
+                        current_arg.push(tokens[j].node.clone());
+                    }
+                }
+                Token::Symbol(',') if paren_depth == 1 => {
+                    args.push(std::mem::take(&mut current_arg));
+                }
+                other => current_arg.push(other.clone()),
+            }
+            j += 1;
+        }
+
+        if args.len() != def.params.len() {
+            return Err(SpanError::new(
+                call_span.lo,
+                format!(
+                    "macro `{}` expects {} argument(s), got {}",
+                    name,
+                    def.params.len(),
+                    args.len()
+                ),
+            ));
+        }
+
+        let suffix = *label_counter;
+        *label_counter += 1;
+
+        let substituted = substitute(&def.body, &def.params, &args, suffix);
+        let spanned: Vec<Spanned<Token>> = substituted
+            .into_iter()
+            .map(|tok| Spanned::new(tok, call_span))
+            .collect();
+
+        let expanded = expand(&spanned, macros, label_counter, depth + 1)?;
+        out.extend(expanded);
+
+        i = j;
+    }
+
+    Ok(out)
+}
+
+/// Replaces each parameter identifier with its argument tokens, and
+/// hygienically renames any identifier immediately following a `.` (the
+/// `.loop_start`/`.if_end`-style labels macros tend to emit) so two
+/// expansions of the same macro never collide.
+fn substitute(
+    body: &[Token],
+    params: &[String],
+    args: &[Vec<Token>],
+    suffix: usize,
+) -> Vec<Token> {
+    let mut out = Vec::new();
+    let mut prev_was_dot = false;
+
+    for tok in body {
+        match tok {
+            Token::Identifier(id) if prev_was_dot => {
+                out.push(Token::Identifier(format!("{}__m{}", id, suffix)));
+            }
+            Token::Identifier(id) => {
+                if let Some(pos) = params.iter().position(|p| p == id) {
+                    out.extend(args[pos].clone());
+                } else {
+                    out.push(tok.clone());
+                }
+            }
+            _ => out.push(tok.clone()),
+        }
+        prev_was_dot = matches!(tok, Token::Symbol('.'));
+    }
+
+    out
+}
@@ -0,0 +1,95 @@
+//! Source-position tracking so codegen failures can point back at the
+//! user's original file instead of silently emitting wrong assembly.
+
+/// A half-open range of character offsets into the *cleaned* token stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub lo: usize,
+    pub hi: usize,
+}
+
+impl Span {
+    pub fn new(lo: usize, hi: usize) -> Self {
+        Span { lo, hi }
+    }
+}
+
+/// Wraps a value with the span of cleaned-source text it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Spanned { node, span }
+    }
+}
+
+/// A diagnostic anchored to a cleaned-source offset, resolved back to
+/// `file:line:col` via a `SourceMap` before it's shown to the user.
+#[derive(Debug, Clone)]
+pub struct SpanError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl SpanError {
+    pub fn new(offset: usize, message: impl Into<String>) -> Self {
+        SpanError {
+            offset,
+            message: message.into(),
+        }
+    }
+}
+
+/// Maps character offsets in the *cleaned* source (post `clean_code`) back
+/// to `(line, col)` in the original file. `clean_code` collapses whitespace
+/// and strips comments, which would otherwise destroy offsets, so it hands
+/// back a `remap` table (`remap[cleaned_offset] == original_offset`) that
+/// this type resolves against.
+pub struct SourceMap {
+    original: String,
+    remap: Vec<usize>,
+}
+
+impl SourceMap {
+    pub fn new(original: String, remap: Vec<usize>) -> Self {
+        SourceMap { original, remap }
+    }
+
+    /// Resolve a cleaned-source offset to a 1-based `(line, col)` pair in
+    /// the original file.
+    pub fn line_col(&self, cleaned_offset: usize) -> (usize, usize) {
+        let chars: Vec<char> = self.original.chars().collect();
+        let orig_offset = self
+            .remap
+            .get(cleaned_offset)
+            .copied()
+            .unwrap_or(chars.len());
+
+        let mut line = 1;
+        let mut col = 1;
+        for &c in chars.iter().take(orig_offset) {
+            if c == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+        }
+        (line, col)
+    }
+
+    /// Format a `file:line:col: error: msg` diagnostic with a caret line
+    /// pointing at the offending column, in the style of a C compiler.
+    pub fn error(&self, filename: &str, cleaned_offset: usize, msg: &str) -> String {
+        let (line, col) = self.line_col(cleaned_offset);
+        let source_line = self.original.lines().nth(line - 1).unwrap_or("");
+        let caret = format!("{}^", " ".repeat(col.saturating_sub(1)));
+        format!(
+            "{filename}:{line}:{col}: error: {msg}\n{source_line}\n{caret}"
+        )
+    }
+}
@@ -0,0 +1,412 @@
+use crate::span::{Span, SpanError, Spanned};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Identifier(String),
+    Operation(String),
+    StringLiteral(String),
+    Symbol(char),
+    Char(char),
+    Number(String),
+    SemiColon,
+    ParenOpen,
+    ParenClose,
+    BraceOpen,
+    BraceClose,
+    SquareOpen,
+    SquareClose,
+}
+
+/// Tokenizes already-`clean_code`d source, recording a `Span` of cleaned-source
+/// offsets alongside each `Token` so callers can trace failures back to the
+/// user's file through a `SourceMap`.
+pub fn tokenize(code: &str) -> Result<Vec<Spanned<Token>>, SpanError> {
+    let mut tokens: Vec<Spanned<Token>> = Vec::new();
+
+    let chars: Vec<char> = code.chars().collect();
+
+    let mut i = 0;
+
+    macro_rules! push {
+        ($tok:expr, $start:expr) => {
+            tokens.push(Spanned::new($tok, Span::new($start, i + 1)))
+        };
+    }
+
+    while i < chars.len() {
+        let start = i;
+        let mut c = chars[i];
+        match c {
+            '(' => push!(Token::ParenOpen, start),
+            ')' => push!(Token::ParenClose, start),
+            '{' => push!(Token::BraceOpen, start),
+            '}' => push!(Token::BraceClose, start),
+            ';' => push!(Token::SemiColon, start),
+
+            '[' => push!(Token::SquareOpen, start),
+            ']' => push!(Token::SquareClose, start),
+
+            '"' => {
+                i += 1;
+                let mut string_literal: String = String::new();
+                loop {
+                    if i >= chars.len() {
+                        return Err(SpanError::new(start, "unterminated string literal"));
+                    }
+                    c = chars[i];
+                    if c == '"' {
+                        break;
+                    }
+                    if c == '\\' {
+                        i += 1;
+                        let esc = chars
+                            .get(i)
+                            .ok_or_else(|| SpanError::new(start, "unterminated string literal"))?;
+                        string_literal.push(decode_escape(*esc));
+                        i += 1;
+                        continue;
+                    }
+                    string_literal.push(c);
+                    i += 1;
+                }
+                push!(Token::StringLiteral(string_literal), start);
+            }
+
+            '\'' => {
+                let mut j = i + 1;
+                let value = match chars.get(j) {
+                    Some('\\') => {
+                        let esc = chars
+                            .get(j + 1)
+                            .ok_or_else(|| SpanError::new(start, "unterminated char literal"))?;
+                        j += 2;
+                        decode_escape(*esc)
+                    }
+                    Some(&ch) => {
+                        j += 1;
+                        ch
+                    }
+                    None => return Err(SpanError::new(start, "unterminated char literal")),
+                };
+                if chars.get(j) != Some(&'\'') {
+                    return Err(SpanError::new(start, "expected closing `'`"));
+                }
+                i = j;
+                push!(Token::Char(value), start);
+            }
+            '<' if chars.get(i + 1) == Some(&'=') => {
+                push!(Token::Operation("<=".to_string()), start);
+                i += 1;
+            }
+            '<' => {
+                let mut include_string = "<".to_string();
+                let start_i = i;
+                i += 1;
+
+                while i < chars.len() {
+                    let c = chars[i];
+                    include_string.push(c);
+                    if c == '>' {
+                        push!(Token::Identifier(include_string.clone()), start);
+                        break;
+                    }
+                    i += 1;
+                }
+
+                // If we never found '>', treat it as an operation
+                if !include_string.ends_with('>') {
+                    i = start_i;
+                    push!(Token::Operation("<".to_string()), start);
+                }
+            }
+            '>' => {
+                let next = chars.get(i + 1).copied().unwrap_or('\0');
+                if next == '=' || next == '>' {
+                    push!(Token::Operation(format!(">{}", next)), start);
+                    i += 1;
+                } else {
+                    push!(Token::Operation(">".to_string()), start);
+                }
+            }
+            '-' => {
+                let next = chars.get(i + 1).copied().unwrap_or('\0');
+                if next != '=' && next != '>' && next != '-' {
+                    push!(Token::Operation("-".to_string()), start);
+                } else {
+                    push!(Token::Operation(format!("-{}", next)), start);
+                    i += 1;
+                }
+            }
+            '+' => {
+                let next = chars.get(i + 1).copied().unwrap_or('\0');
+                if next == '=' || next == '+' {
+                    push!(Token::Operation(format!("+{}", next)), start);
+                    i += 1;
+                } else {
+                    push!(Token::Operation("+".to_string()), start);
+                }
+            }
+            '*' => {
+                let next = chars.get(i + 1).copied().unwrap_or('\0');
+                if next != '=' {
+                    push!(Token::Operation("*".to_string()), start);
+                } else {
+                    push!(Token::Operation("*=".to_string()), start);
+                    i += 1;
+                }
+            }
+            '/' => {
+                let next = chars.get(i + 1).copied().unwrap_or('\0');
+                if next != '=' {
+                    push!(Token::Operation("/".to_string()), start);
+                } else {
+                    push!(Token::Operation("/=".to_string()), start);
+                }
+            }
+            '=' => {
+                let next = chars.get(i + 1).copied().unwrap_or('\0');
+                if next != '=' {
+                    push!(Token::Operation("=".to_string()), start);
+                } else {
+                    push!(Token::Operation("==".to_string()), start);
+                    i += 1;
+                }
+            }
+            '!' => {
+                let n1 = chars.get(i + 1).copied().unwrap_or('\0');
+                let n2 = chars.get(i + 2).copied().unwrap_or('\0');
+                if n1 == '-' && n2 == '>' {
+                    push!(Token::Operation("!->".to_string()), start);
+                    i += 3;
+                } else if n1 != '=' {
+                    push!(Token::Operation("!".to_string()), start);
+                    i += 1;
+                } else {
+                    push!(Token::Operation("!=".to_string()), start);
+                    i += 1;
+                }
+            }
+            '&' => {
+                let next = chars.get(i + 1).copied().unwrap_or('\0');
+                if next != '&' {
+                    push!(Token::Operation("&".to_string()), start);
+                } else {
+                    push!(Token::Operation("&&".to_string()), start);
+                    i += 1;
+                }
+            }
+            '|' => {
+                let next = chars.get(i + 1).copied().unwrap_or('\0');
+                if next != '|' {
+                    push!(Token::Operation("|".to_string()), start);
+                } else {
+                    push!(Token::Operation("||".to_string()), start);
+                    i += 1;
+                }
+            }
+            ':' => {
+                let next = chars.get(i + 1).copied().unwrap_or('\0');
+                if next == '=' {
+                    push!(Token::Operation(":=".to_string()), start);
+                    i += 1;
+                }
+            }
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+
+            _ if c.is_alphabetic() || c == '_' => {
+                let mut identifier = String::new();
+
+                while c.is_alphabetic() || c == '_' {
+                    identifier += c.to_string().as_str();
+                    i += 1;
+                    match chars.get(i) {
+                        Some(&next) => c = next,
+                        None => break,
+                    }
+                }
+                i -= 1;
+                push!(Token::Identifier(identifier), start);
+            }
+            _ if c.is_ascii_digit() => {
+                let mut number = String::new();
+                let next = chars.get(i + 1).copied().unwrap_or('\0');
+
+                if c == '0' && (next == 'x' || next == 'X') {
+                    number.push(c);
+                    number.push(next);
+                    i += 2;
+                    while chars.get(i).is_some_and(|d| d.is_ascii_hexdigit() || *d == '_') {
+                        number.push(chars[i]);
+                        i += 1;
+                    }
+                    i -= 1;
+                } else if c == '0' && (next == 'b' || next == 'B') {
+                    number.push(c);
+                    number.push(next);
+                    i += 2;
+                    while matches!(chars.get(i), Some('0') | Some('1') | Some('_')) {
+                        number.push(chars[i]);
+                        i += 1;
+                    }
+                    i -= 1;
+                } else {
+                    while c.is_ascii_digit() || c == '_' {
+                        number += c.to_string().as_str();
+                        i += 1;
+                        match chars.get(i) {
+                            Some(&next) => c = next,
+                            None => break,
+                        }
+                    }
+                    if c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit()) {
+                        number.push(c);
+                        i += 1;
+                        c = chars[i];
+                        while c.is_ascii_digit() || c == '_' {
+                            number += c.to_string().as_str();
+                            i += 1;
+                            match chars.get(i) {
+                                Some(&next) => c = next,
+                                None => break,
+                            }
+                        }
+                    }
+                    i -= 1;
+                }
+                push!(Token::Number(number), start);
+            }
+
+            _ => push!(Token::Symbol(c), start),
+        }
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+/// Maps the character after a `\` in a string or char literal to the byte it
+/// stands for, passing through anything it doesn't recognize unchanged (so a
+/// stray `\q` just becomes `q` rather than a hard error).
+fn decode_escape(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        '\\' => '\\',
+        '"' => '"',
+        '\'' => '\'',
+        other => other,
+    }
+}
+
+/// Strips `//` comments and collapses runs of whitespace to a single space,
+/// returning the cleaned source alongside a `remap` table where
+/// `remap[cleaned_offset] == original_offset`, so spans taken against the
+/// cleaned text can still be traced back to the file the user wrote.
+pub fn clean_code(code_raw: &str) -> (String, Vec<usize>) {
+    let chars: Vec<char> = code_raw.chars().collect();
+
+    let mut stage: Vec<char> = Vec::new();
+    let mut remap: Vec<usize> = Vec::new();
+    let mut i = 0;
+    let mut last_was_space = false;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+
+        if c.is_whitespace() {
+            if !last_was_space && !stage.is_empty() {
+                stage.push(' ');
+                remap.push(i);
+                last_was_space = true;
+            }
+            i += 1;
+            continue;
+        }
+
+        stage.push(c);
+        remap.push(i);
+        last_was_space = false;
+        i += 1;
+    }
+
+    if stage.last() == Some(&' ') {
+        stage.pop();
+        remap.pop();
+    }
+
+    strip_adjacent_spaces(&stage, &remap)
+}
+
+/// Drops the space in `"; "`, `"{ "`, `" }"` and `" )"`, the same trims the
+/// old regex-based `clean_code` applied, but offset-by-offset so the remap
+/// table stays aligned with what survives.
+fn strip_adjacent_spaces(chars: &[char], remap: &[usize]) -> (String, Vec<usize>) {
+    let mut out = String::with_capacity(chars.len());
+    let mut out_remap = Vec::with_capacity(remap.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == ' ' {
+            let prev = if i > 0 { Some(chars[i - 1]) } else { None };
+            let next = chars.get(i + 1).copied();
+            let drop_after = matches!(prev, Some(';') | Some('{'));
+            let drop_before = matches!(next, Some('}') | Some(')'));
+            if drop_after || drop_before {
+                i += 1;
+                continue;
+            }
+        }
+        out.push(c);
+        out_remap.push(remap[i]);
+        i += 1;
+    }
+
+    (out, out_remap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toks(code: &str) -> Vec<Token> {
+        tokenize(code).unwrap().into_iter().map(|t| t.node).collect()
+    }
+
+    #[test]
+    fn string_literal_decodes_escapes() {
+        assert_eq!(toks("\"a\\tb\\n\""), vec![Token::StringLiteral("a\tb\n".to_string())]);
+    }
+
+    #[test]
+    fn char_literal_decodes_escaped_quote() {
+        assert_eq!(toks("'\\''"), vec![Token::Char('\'')]);
+    }
+
+    #[test]
+    fn number_accepts_hex_literal() {
+        assert_eq!(toks("0xFF"), vec![Token::Number("0xFF".to_string())]);
+    }
+
+    #[test]
+    fn number_accepts_float_literal() {
+        assert_eq!(toks("3.14"), vec![Token::Number("3.14".to_string())]);
+    }
+
+    #[test]
+    fn number_accepts_digit_separators() {
+        assert_eq!(toks("1_000"), vec![Token::Number("1_000".to_string())]);
+    }
+}